@@ -0,0 +1,91 @@
+use core::fmt::{self, Write};
+use lazy_static::lazy_static;
+use spin::Mutex;
+use x86_64::instructions::port::Port;
+
+const COM1: u16 = 0x3F8;
+
+pub struct SerialPort {
+    data: Port<u8>,
+    interrupt_enable: Port<u8>,
+    fifo_control: Port<u8>,
+    line_control: Port<u8>,
+    modem_control: Port<u8>,
+    line_status: Port<u8>,
+}
+
+impl SerialPort {
+    const fn new(base: u16) -> SerialPort {
+        SerialPort {
+            data: Port::new(base),
+            interrupt_enable: Port::new(base + 1),
+            fifo_control: Port::new(base + 2),
+            line_control: Port::new(base + 3),
+            modem_control: Port::new(base + 4),
+            line_status: Port::new(base + 5),
+        }
+    }
+
+    fn init(&mut self) {
+        unsafe {
+            self.interrupt_enable.write(0x00); // disable interrupts
+            self.line_control.write(0x80); // enable DLAB to set the baud divisor
+            self.data.write(0x03); // divisor low byte: 38400 baud
+            self.interrupt_enable.write(0x00); // divisor high byte
+            self.line_control.write(0x03); // 8 bits, no parity, one stop bit
+            self.fifo_control.write(0xc7); // enable FIFO, clear it, 14-byte threshold
+            self.modem_control.write(0x0b); // DTR, RTS and OUT2 set
+        }
+    }
+
+    fn line_status(&mut self) -> u8 {
+        unsafe { self.line_status.read() }
+    }
+
+    fn wait_for_transmit_empty(&mut self) {
+        while self.line_status() & 0x20 == 0 {}
+    }
+
+    fn write_byte(&mut self, byte: u8) {
+        self.wait_for_transmit_empty();
+        unsafe {
+            self.data.write(byte);
+        }
+    }
+}
+
+impl fmt::Write for SerialPort {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            self.write_byte(byte);
+        }
+        Ok(())
+    }
+}
+
+lazy_static! {
+    pub static ref SERIAL1: Mutex<SerialPort> = {
+        let mut port = SerialPort::new(COM1);
+        port.init();
+        Mutex::new(port)
+    };
+}
+
+#[macro_export]
+macro_rules! serial_print {
+    ($($arg:tt)*) => ($crate::serial::_print(format_args!($($arg)*)));
+}
+
+#[macro_export]
+macro_rules! serial_println {
+    () => ($crate::serial_print!("\n"));
+    ($($arg:tt)*) => ($crate::serial_print!("{}\n", format_args!($($arg)*)));
+}
+
+#[doc(hidden)]
+pub fn _print(args: fmt::Arguments) {
+    SERIAL1
+        .lock()
+        .write_fmt(args)
+        .expect("printing to serial failed");
+}
@@ -1,6 +1,7 @@
 use crate::vga_buffer::Color::{Black, LightGrey, Pink};
 use lazy_static::lazy_static;
 use volatile::Volatile;
+use x86_64::instructions::port::Port;
 
 #[allow(dead_code)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -32,6 +33,52 @@ impl ColorCode {
     fn new(foreground: Color, background: Color) -> ColorCode {
         ColorCode((background as u8) << 4 | (foreground as u8))
     }
+
+    fn foreground_bits(self) -> u8 {
+        self.0 & 0x0f
+    }
+
+    fn with_foreground_bits(self, bits: u8) -> ColorCode {
+        ColorCode((self.0 & 0xf0) | (bits & 0x0f))
+    }
+
+    fn with_background(self, background: Color) -> ColorCode {
+        ColorCode(((background as u8) << 4) | (self.0 & 0x0f))
+    }
+}
+
+fn ansi_color(index: u8, bright: bool) -> Color {
+    match (index, bright) {
+        (0, false) => Color::Black,
+        (1, false) => Color::Red,
+        (2, false) => Color::Green,
+        (3, false) => Color::Brown,
+        (4, false) => Color::Blue,
+        (5, false) => Color::Magenta,
+        (6, false) => Color::Cyan,
+        (7, false) => Color::LightGrey,
+        (0, true) => Color::DarkGrey,
+        (1, true) => Color::LightRed,
+        (2, true) => Color::LightGreen,
+        (3, true) => Color::Yellow,
+        (4, true) => Color::LightBlue,
+        (5, true) => Color::Pink,
+        (6, true) => Color::LightCyan,
+        (7, true) => Color::White,
+        _ => Color::LightGrey,
+    }
+}
+
+const MAX_CSI_PARAMS: usize = 8;
+
+// Has to live on Writer itself since an escape sequence can be split
+// across separate write_byte calls
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AnsiState {
+    Ground,
+    Escape,
+    CsiEntry,
+    CsiParam,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -50,70 +97,261 @@ struct Buffer {
     chars: [[Volatile<ScreenChar>; BUFFER_WIDTH]; BUFFER_HEIGHT],
 }
 
+const BLANK_CHAR: ScreenChar = ScreenChar {
+    ascii_char: b' ',
+    color_code: ColorCode(0x07),
+};
+const BLANK_ROW: [ScreenChar; BUFFER_WIDTH] = [BLANK_CHAR; BUFFER_WIDTH];
+
+const HISTORY_CAPACITY: usize = 200;
+
+struct History {
+    rows: [[ScreenChar; BUFFER_WIDTH]; HISTORY_CAPACITY],
+    start: usize,
+    len: usize,
+}
+
+impl History {
+    const fn new() -> History {
+        History {
+            rows: [BLANK_ROW; HISTORY_CAPACITY],
+            start: 0,
+            len: 0,
+        }
+    }
+
+    fn push(&mut self, row: [ScreenChar; BUFFER_WIDTH]) {
+        let idx = (self.start + self.len) % HISTORY_CAPACITY;
+        self.rows[idx] = row;
+        if self.len < HISTORY_CAPACITY {
+            self.len += 1;
+        } else {
+            self.start = (self.start + 1) % HISTORY_CAPACITY;
+        }
+    }
+
+    // back_from_end 0 = newest row still kept
+    fn get(&self, back_from_end: usize) -> Option<&[ScreenChar; BUFFER_WIDTH]> {
+        if back_from_end >= self.len {
+            return None;
+        }
+        let idx = (self.start + self.len - 1 - back_from_end) % HISTORY_CAPACITY;
+        Some(&self.rows[idx])
+    }
+}
+
 // Implement writer
 pub struct Writer {
-    column_pos: usize,
+    cursor_row: usize,
+    cursor_col: usize,
     //Store the position of latest char
     color_code: ColorCode,
+    default_color_code: ColorCode,
     buffer: &'static mut Buffer, //ยังงงๆอยู่ว่าทำไมต้อง ref มา
+    ansi_state: AnsiState,
+    csi_params: [u16; MAX_CSI_PARAMS],
+    csi_param_count: usize,
+    history: History,
+    live_snapshot: [[ScreenChar; BUFFER_WIDTH]; BUFFER_HEIGHT],
+    scroll_offset: usize,
+    bold: bool,
 }
 
 impl Writer {
     pub fn write_byte(&mut self, byte: u8) {
-        match byte {
-            b'\n' => self.new_line(),
-            b'\t' => self.indent(),
-            byte => {
-                // In case of any other bytes, we will put into byte variable
-                if self.column_pos >= BUFFER_WIDTH {
-                    self.new_line();
+        if self.scroll_offset != 0 {
+            self.scroll_to_bottom();
+        }
+        match self.ansi_state {
+            AnsiState::Ground => match byte {
+                0x1b => self.ansi_state = AnsiState::Escape,
+                b'\n' => self.new_line(),
+                b'\t' => self.indent(),
+                byte => {
+                    // In case of any other bytes, we will put into byte variable
+                    if self.cursor_col >= BUFFER_WIDTH {
+                        self.new_line();
+                    }
+
+                    let row = self.cursor_row;
+                    let col = self.cursor_col;
+
+                    self.buffer.chars[row][col].write(ScreenChar {
+                        ascii_char: byte,
+                        color_code: self.color_code,
+                    });
+                    self.cursor_col += 1;
+                    self.update_hardware_cursor();
+                }
+            },
+            AnsiState::Escape => match byte {
+                b'[' => {
+                    self.ansi_state = AnsiState::CsiEntry;
+                    self.csi_params = [0; MAX_CSI_PARAMS];
+                    self.csi_param_count = 0;
+                }
+                _ => self.ansi_state = AnsiState::Ground,
+            },
+            AnsiState::CsiEntry | AnsiState::CsiParam => match byte {
+                b'0'..=b'9' => {
+                    self.ansi_state = AnsiState::CsiParam;
+                    if self.csi_param_count == 0 {
+                        self.csi_param_count = 1;
+                    }
+                    if let Some(param) = self.csi_params.get_mut(self.csi_param_count - 1) {
+                        *param = param.saturating_mul(10).saturating_add((byte - b'0') as u16);
+                    }
+                }
+                b';' => {
+                    self.ansi_state = AnsiState::CsiParam;
+                    if self.csi_param_count == 0 {
+                        self.csi_param_count = 1; // finalize the implicit first (empty) param
+                    }
+                    if self.csi_param_count < MAX_CSI_PARAMS {
+                        self.csi_param_count += 1; // open a fresh slot for what follows
+                    }
                 }
+                0x40..=0x7e => {
+                    self.dispatch_csi(byte);
+                    self.ansi_state = AnsiState::Ground;
+                }
+                _ => self.ansi_state = AnsiState::Ground,
+            },
+        }
+    }
 
-                let row = BUFFER_HEIGHT - 1; // The default position of buffer
-                let col = self.column_pos;
+    fn dispatch_csi(&mut self, final_byte: u8) {
+        match final_byte {
+            b'm' => self.handle_sgr(),
+            b'A' => self.cursor_up(self.csi_param(0, 1).max(1) as usize),
+            b'B' => self.cursor_down(self.csi_param(0, 1).max(1) as usize),
+            b'C' => self.cursor_right(self.csi_param(0, 1).max(1) as usize),
+            b'D' => self.cursor_left(self.csi_param(0, 1).max(1) as usize),
+            b'H' | b'f' => {
+                let row = self.csi_param(0, 1).saturating_sub(1) as usize;
+                let col = self.csi_param(1, 1).saturating_sub(1) as usize;
+                self.set_cursor(row, col);
+            }
+            b'J' => match self.csi_param(0, 0) {
+                0 => self.clear_from_cursor_to_end_of_screen(),
+                1 => self.clear_from_start_of_screen_to_cursor(),
+                _ => {
+                    // Unlike clear_screen(), modes 2/3 don't move the cursor.
+                    for row in 0..BUFFER_HEIGHT {
+                        self.clear_row(row);
+                    }
+                }
+            },
+            b'K' => match self.csi_param(0, 0) {
+                0 => self.clear_line_from_cursor_to_end(),
+                1 => self.clear_line_from_start_to_cursor(),
+                _ => self.clear_row(self.cursor_row),
+            },
+            _ => {} // unknown final byte: drop the sequence silently
+        }
+    }
+
+    fn csi_param(&self, index: usize, default: u16) -> u16 {
+        if index >= self.csi_param_count {
+            return default;
+        }
+        match self.csi_params[index] {
+            0 => default,
+            value => value,
+        }
+    }
+
+    fn handle_sgr(&mut self) {
+        if self.csi_param_count == 0 {
+            self.color_code = self.default_color_code;
+            self.bold = false;
+            return;
+        }
 
-                self.buffer.chars[row][col].write(ScreenChar {
-                    ascii_char: byte,
-                    color_code: self.color_code,
-                });
-                self.column_pos += 1;
+        for i in 0..self.csi_param_count {
+            match self.csi_params[i] {
+                0 => {
+                    self.color_code = self.default_color_code;
+                    self.bold = false;
+                }
+                1 => self.set_bold(true),
+                code @ 30..=37 => {
+                    let base = ansi_color((code - 30) as u8, false) as u8;
+                    self.color_code = self
+                        .color_code
+                        .with_foreground_bits(base | if self.bold { 0x08 } else { 0 });
+                }
+                code @ 40..=47 => {
+                    self.color_code = self
+                        .color_code
+                        .with_background(ansi_color((code - 40) as u8, false));
+                }
+                code @ 90..=97 => {
+                    let base = ansi_color((code - 90) as u8, false) as u8;
+                    self.color_code = self.color_code.with_foreground_bits(base | 0x08);
+                }
+                _ => {}
             }
         }
     }
 
+    // Colors 0-7 in the VGA palette sit at the same index as their bright
+    // counterpart at index+8, so bold can be (re)applied to whatever
+    // foreground is already set instead of depending on the order `1` and
+    // `3x`/`9x` arrived in.
+    fn set_bold(&mut self, bold: bool) {
+        self.bold = bold;
+        let base = self.color_code.foreground_bits() & 0x07;
+        self.color_code = self
+            .color_code
+            .with_foreground_bits(base | if bold { 0x08 } else { 0 });
+    }
+
     fn write_string(&mut self, s: &str) {
         for byte in s.bytes() {
             match byte {
-                0x20..=0x7e | b'\n' | b'\t' => self.write_byte(byte),
+                0x20..=0x7e | b'\n' | b'\t' | 0x1b => self.write_byte(byte),
                 _ => self.write_byte(0xfe),
             }
         }
     }
 
     fn new_line(&mut self) {
-        for row in 1..BUFFER_HEIGHT {
-            for col in 0..BUFFER_WIDTH {
-                let character = self.buffer.chars[row][col].read();
-                self.buffer.chars[row - 1][col].write(character);
+        if self.cursor_row + 1 >= BUFFER_HEIGHT {
+            let mut top_row = BLANK_ROW;
+            for (col, slot) in top_row.iter_mut().enumerate() {
+                *slot = self.buffer.chars[0][col].read();
+            }
+            self.history.push(top_row);
+
+            for row in 1..BUFFER_HEIGHT {
+                for col in 0..BUFFER_WIDTH {
+                    let character = self.buffer.chars[row][col].read();
+                    self.buffer.chars[row - 1][col].write(character);
+                }
             }
+            self.clear_row(BUFFER_HEIGHT - 1);
+        } else {
+            self.cursor_row += 1;
         }
-        self.clear_row(BUFFER_HEIGHT - 1);
-        self.column_pos = 0;
+        self.cursor_col = 0;
+        self.update_hardware_cursor();
     }
 
     fn indent(&mut self) {
-        // I must find next column_pos that divisible by INDENT_SIZE
+        // I must find next cursor_col that divisible by INDENT_SIZE
         // Here is the equation that comes up on my mind
-        // self.column_pos = self.column_pos + (INDENT_SIZE - (self.column_pos % INDENT_SIZE))
+        // self.cursor_col = self.cursor_col + (INDENT_SIZE - (self.cursor_col % INDENT_SIZE))
         // And here is the gemini help me to simplify the equation
-        self.column_pos = {
-            if self.column_pos < BUFFER_WIDTH - INDENT_SIZE {
-                ((self.column_pos / INDENT_SIZE) + 1) * INDENT_SIZE
+        self.cursor_col = {
+            if self.cursor_col < BUFFER_WIDTH - INDENT_SIZE {
+                ((self.cursor_col / INDENT_SIZE) + 1) * INDENT_SIZE
             } else {
                 BUFFER_WIDTH - 1
                 // May be new line instead?
             }
-        }
+        };
+        self.update_hardware_cursor();
     }
 
     fn clear_row(&mut self, row: usize) {
@@ -129,6 +367,146 @@ impl Writer {
     fn set_color(&mut self, color_code: ColorCode){
         self.color_code = color_code;
     }
+
+    pub fn set_cursor(&mut self, row: usize, col: usize) {
+        self.cursor_row = row.min(BUFFER_HEIGHT - 1);
+        self.cursor_col = col.min(BUFFER_WIDTH - 1);
+        self.update_hardware_cursor();
+    }
+
+    pub fn cursor_up(&mut self, n: usize) {
+        self.cursor_row = self.cursor_row.saturating_sub(n);
+        self.update_hardware_cursor();
+    }
+
+    pub fn cursor_down(&mut self, n: usize) {
+        self.cursor_row = (self.cursor_row + n).min(BUFFER_HEIGHT - 1);
+        self.update_hardware_cursor();
+    }
+
+    pub fn cursor_left(&mut self, n: usize) {
+        self.cursor_col = self.cursor_col.saturating_sub(n);
+        self.update_hardware_cursor();
+    }
+
+    pub fn cursor_right(&mut self, n: usize) {
+        self.cursor_col = (self.cursor_col + n).min(BUFFER_WIDTH - 1);
+        self.update_hardware_cursor();
+    }
+
+    pub fn clear_screen(&mut self) {
+        for row in 0..BUFFER_HEIGHT {
+            self.clear_row(row);
+        }
+        self.set_cursor(0, 0);
+    }
+
+    fn clear_line_from_cursor_to_end(&mut self) {
+        let blank = ScreenChar {
+            ascii_char: b' ',
+            color_code: self.color_code,
+        };
+        let row = self.cursor_row;
+        for col in self.cursor_col..BUFFER_WIDTH {
+            self.buffer.chars[row][col].write(blank);
+        }
+    }
+
+    fn clear_line_from_start_to_cursor(&mut self) {
+        let blank = ScreenChar {
+            ascii_char: b' ',
+            color_code: self.color_code,
+        };
+        let row = self.cursor_row;
+        for col in 0..=self.cursor_col.min(BUFFER_WIDTH - 1) {
+            self.buffer.chars[row][col].write(blank);
+        }
+    }
+
+    fn clear_from_cursor_to_end_of_screen(&mut self) {
+        self.clear_line_from_cursor_to_end();
+        for row in (self.cursor_row + 1)..BUFFER_HEIGHT {
+            self.clear_row(row);
+        }
+    }
+
+    fn clear_from_start_of_screen_to_cursor(&mut self) {
+        for row in 0..self.cursor_row {
+            self.clear_row(row);
+        }
+        self.clear_line_from_start_to_cursor();
+    }
+
+    pub fn scroll_up(&mut self, lines: usize) {
+        if self.scroll_offset == 0 {
+            self.snapshot_live();
+        }
+        self.scroll_offset = (self.scroll_offset + lines).min(self.history.len);
+        self.repaint_scrollback();
+    }
+
+    pub fn scroll_down(&mut self, lines: usize) {
+        if self.scroll_offset == 0 {
+            return;
+        }
+        self.scroll_offset = self.scroll_offset.saturating_sub(lines);
+        if self.scroll_offset == 0 {
+            self.scroll_to_bottom();
+        } else {
+            self.repaint_scrollback();
+        }
+    }
+
+    pub fn scroll_to_bottom(&mut self) {
+        if self.scroll_offset == 0 {
+            return;
+        }
+        for row in 0..BUFFER_HEIGHT {
+            for col in 0..BUFFER_WIDTH {
+                self.buffer.chars[row][col].write(self.live_snapshot[row][col]);
+            }
+        }
+        self.scroll_offset = 0;
+        self.update_hardware_cursor();
+    }
+
+    fn snapshot_live(&mut self) {
+        for row in 0..BUFFER_HEIGHT {
+            for col in 0..BUFFER_WIDTH {
+                self.live_snapshot[row][col] = self.buffer.chars[row][col].read();
+            }
+        }
+    }
+
+    fn repaint_scrollback(&mut self) {
+        let bottom_index = self.history.len + BUFFER_HEIGHT - 1 - self.scroll_offset;
+        let top_index = bottom_index + 1 - BUFFER_HEIGHT;
+        for r in 0..BUFFER_HEIGHT {
+            let seq_index = top_index + r;
+            let row_chars = if seq_index < self.history.len {
+                let back = self.history.len - 1 - seq_index;
+                *self.history.get(back).unwrap_or(&BLANK_ROW)
+            } else {
+                self.live_snapshot[seq_index - self.history.len]
+            };
+            for (col, &ch) in row_chars.iter().enumerate() {
+                self.buffer.chars[r][col].write(ch);
+            }
+        }
+    }
+
+    // index reg 0x0F/0x0E on port 0x3D4 select low/high cursor pos byte, data goes through 0x3D5
+    fn update_hardware_cursor(&self) {
+        let pos = (self.cursor_row * BUFFER_WIDTH + self.cursor_col) as u16;
+        let mut index_port: Port<u8> = Port::new(0x3D4);
+        let mut data_port: Port<u8> = Port::new(0x3D5);
+        unsafe {
+            index_port.write(0x0Fu8);
+            data_port.write((pos & 0xFF) as u8);
+            index_port.write(0x0Eu8);
+            data_port.write(((pos >> 8) & 0xFF) as u8);
+        }
+    }
 }
 
 use core::fmt::{self, Write};
@@ -152,9 +530,18 @@ use spin::Mutex;
 
 lazy_static! {
     pub static ref WRITER: Mutex<Writer> = Mutex::new(Writer {
-        column_pos: 0,
+        cursor_row: 0,
+        cursor_col: 0,
         color_code: ColorCode::new(Color::LightGrey, Color::Black),
+        default_color_code: ColorCode::new(Color::LightGrey, Color::Black),
         buffer: unsafe { &mut *(0xb8000 as *mut Buffer) },
+        ansi_state: AnsiState::Ground,
+        csi_params: [0; MAX_CSI_PARAMS],
+        csi_param_count: 0,
+        history: History::new(),
+        live_snapshot: [BLANK_ROW; BUFFER_HEIGHT],
+        scroll_offset: 0,
+        bold: false,
     });
 }
 
@@ -180,9 +567,20 @@ macro_rules! println {
     ($($arg:tt)*) => ($crate::print!("{}\n", format_args!($($arg)*)));
 }
 
+use core::sync::atomic::{AtomicBool, Ordering};
+
+static SERIAL_TEE: AtomicBool = AtomicBool::new(false);
+
+pub fn set_serial_tee(enabled: bool) {
+    SERIAL_TEE.store(enabled, Ordering::Relaxed);
+}
+
 #[doc(hidden)]
 pub fn _print(args: fmt::Arguments) {
     WRITER.lock().write_fmt(args).unwrap();
+    if SERIAL_TEE.load(Ordering::Relaxed) {
+        crate::serial::_print(args);
+    }
 }
 
 pub fn _print_panic(args: fmt::Arguments) {
@@ -201,12 +599,79 @@ pub fn _print_important(args: fmt::Arguments) {
     WRITER.lock().set_color(orig_color);
 }
 
+#[macro_export]
+macro_rules! hexdump {
+    ($bytes:expr) => {
+        $crate::vga_buffer::hexdump($bytes)
+    };
+}
+
+fn hexdump_color(byte: u8) -> ColorCode {
+    match byte {
+        0x00 => ColorCode::new(Color::DarkGrey, Color::Black),
+        0x09 | 0x0a | 0x0d | 0x20 => ColorCode::new(Color::Green, Color::Black),
+        0x21..=0x7e => ColorCode::new(Color::Cyan, Color::Black),
+        _ => ColorCode::new(Color::Yellow, Color::Black),
+    }
+}
+
+pub fn hexdump(bytes: &[u8]) {
+    let mut writer = WRITER.lock();
+    let original_color = writer.color_code;
+
+    for (row, chunk) in bytes.chunks(16).enumerate() {
+        writer.set_color(original_color);
+        write!(writer, "{:08x}  ", row * 16).unwrap();
+
+        for i in 0..16 {
+            match chunk.get(i) {
+                Some(byte) => {
+                    writer.set_color(hexdump_color(*byte));
+                    write!(writer, "{:02x} ", byte).unwrap();
+                }
+                None => {
+                    writer.set_color(original_color);
+                    write!(writer, "   ").unwrap();
+                }
+            }
+            if i == 7 {
+                write!(writer, " ").unwrap();
+            }
+        }
+
+        writer.set_color(original_color);
+        write!(writer, " |").unwrap();
+        for byte in chunk {
+            writer.set_color(hexdump_color(*byte));
+            let ascii = if (0x20..=0x7e).contains(byte) {
+                *byte as char
+            } else {
+                '.'
+            };
+            write!(writer, "{}", ascii).unwrap();
+        }
+        writer.set_color(original_color);
+        writeln!(writer, "|").unwrap();
+    }
+
+    writer.set_color(original_color);
+}
+
 #[allow(dead_code)]
 pub fn demo_printing() {
     let mut writer = Writer {
-        column_pos: 0,
+        cursor_row: 0,
+        cursor_col: 0,
         color_code: ColorCode::new(LightGrey, Black),
+        default_color_code: ColorCode::new(LightGrey, Black),
         buffer: unsafe { &mut *(0xb8000 as *mut Buffer) },
+        ansi_state: AnsiState::Ground,
+        csi_params: [0; MAX_CSI_PARAMS],
+        csi_param_count: 0,
+        history: History::new(),
+        live_snapshot: [BLANK_ROW; BUFFER_HEIGHT],
+        scroll_offset: 0,
+        bold: false,
     };
 
     writer.write_byte(b'H');